@@ -0,0 +1,184 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A minimal Aho-Corasick automaton, used by matchers that need to search
+//! for many substrings in a single pass over the text rather than rescanning
+//! it once per pattern.
+
+use std::collections::{HashMap, VecDeque};
+
+/// A compiled Aho-Corasick automaton over a fixed set of patterns.
+///
+/// Construction builds a trie of the patterns, then computes a failure link
+/// for each node (pointing to the node representing the longest proper
+/// suffix of the node's prefix which is also a prefix of some pattern) and
+/// merges each node's output set with its failure link's, so that scanning
+/// a text of length `n` against `k` patterns, with `m` total matches, takes
+/// `O(n + m)` regardless of `k`.
+#[derive(Debug)]
+pub(crate) struct AhoCorasick {
+    patterns: Vec<String>,
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    goto: HashMap<u8, usize>,
+    fail: usize,
+    /// Indices into `patterns` of every pattern ending at this node or at
+    /// any node reachable via this node's failure-link chain.
+    output: Vec<usize>,
+}
+
+impl AhoCorasick {
+    /// Builds an automaton recognizing `patterns`. Patterns are deduplicated,
+    /// and an empty pattern set yields an automaton that never matches
+    /// anything.
+    pub(crate) fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut deduped: Vec<String> = Vec::new();
+        for pattern in patterns {
+            if !deduped.iter().any(|existing| existing == pattern) {
+                deduped.push(pattern.to_string());
+            }
+        }
+
+        let mut nodes = vec![Node::default()];
+        for (pattern_index, pattern) in deduped.iter().enumerate() {
+            let mut current = 0;
+            for &byte in pattern.as_bytes() {
+                current = *nodes[current].goto.entry(byte).or_insert_with(|| {
+                    nodes.push(Node::default());
+                    nodes.len() - 1
+                });
+            }
+            nodes[current].output.push(pattern_index);
+        }
+
+        let mut queue = VecDeque::new();
+        for &child in nodes[0].goto.values() {
+            // `fail` already defaults to the root, but depth-1 nodes still
+            // need to inherit the root's own output (e.g. an empty pattern,
+            // which lands directly on the root) the same way deeper nodes
+            // inherit their failure target's output below.
+            let inherited = nodes[0].output.clone();
+            nodes[child].output.extend(inherited);
+            queue.push_back(child);
+        }
+        while let Some(node_index) = queue.pop_front() {
+            let children: Vec<(u8, usize)> =
+                nodes[node_index].goto.iter().map(|(&byte, &child)| (byte, child)).collect();
+            for (byte, child) in children {
+                let mut fallback = nodes[node_index].fail;
+                let fail_target = loop {
+                    match nodes[fallback].goto.get(&byte) {
+                        // A node is never its own failure link.
+                        Some(&next) if next != child => break next,
+                        _ if fallback == 0 => break 0,
+                        _ => fallback = nodes[fallback].fail,
+                    }
+                };
+                nodes[child].fail = fail_target;
+                let inherited = nodes[fail_target].output.clone();
+                nodes[child].output.extend(inherited);
+                queue.push_back(child);
+            }
+        }
+
+        Self { patterns: deduped, nodes }
+    }
+
+    /// The number of distinct patterns in the automaton, after deduplication.
+    pub(crate) fn pattern_count(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub(crate) fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// Scans `text` once and returns, indexed the same as [`Self::patterns`],
+    /// whether each pattern occurs somewhere in `text`.
+    pub(crate) fn find_all(&self, text: &str) -> Vec<bool> {
+        let mut found = vec![false; self.patterns.len()];
+        let mut state = 0;
+        // An empty pattern lands on the root and is "found" before any byte
+        // is consumed, including when `text` itself is empty -- the loop
+        // below would otherwise never run at all in that case.
+        for &pattern_index in &self.nodes[0].output {
+            found[pattern_index] = true;
+        }
+        for &byte in text.as_bytes() {
+            while state != 0 && !self.nodes[state].goto.contains_key(&byte) {
+                state = self.nodes[state].fail;
+            }
+            state = self.nodes[state].goto.get(&byte).copied().unwrap_or(0);
+            for &pattern_index in &self.nodes[state].output {
+                found[pattern_index] = true;
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_single_pattern() {
+        let automaton = AhoCorasick::new(["fox"]);
+        assert_eq!(automaton.find_all("the quick brown fox"), vec![true]);
+    }
+
+    #[test]
+    fn reports_missing_pattern() {
+        let automaton = AhoCorasick::new(["dog"]);
+        assert_eq!(automaton.find_all("the quick brown fox"), vec![false]);
+    }
+
+    #[test]
+    fn finds_overlapping_patterns_via_failure_links() {
+        // "she" and "he" overlap at the "he" suffix, exercising the failure
+        // link from the "she" branch back into the "he" branch.
+        let automaton = AhoCorasick::new(["he", "she", "his", "hers"]);
+        let found = automaton.find_all("ushers");
+        assert_eq!(automaton.patterns(), ["he", "she", "his", "hers"]);
+        assert_eq!(found, vec![true, true, false, true]);
+    }
+
+    #[test]
+    fn empty_pattern_set_matches_nothing() {
+        let automaton = AhoCorasick::new(Vec::<&str>::new());
+        assert_eq!(automaton.pattern_count(), 0);
+        assert!(automaton.find_all("anything").is_empty());
+    }
+
+    #[test]
+    fn empty_pattern_within_a_non_empty_set_always_matches() {
+        // The empty pattern lands on the root; it must be reported as found
+        // both when the first byte of the text takes the scan straight into
+        // a depth-1 node (no failure-link walk involved) and when the text
+        // is empty altogether (the scan never consumes a byte).
+        let automaton = AhoCorasick::new(["", "fox"]);
+        assert_eq!(automaton.find_all("a fox"), vec![true, true]);
+        assert_eq!(automaton.find_all("no match here"), vec![true, false]);
+        assert_eq!(automaton.find_all(""), vec![true, false]);
+    }
+
+    #[test]
+    fn deduplicates_patterns() {
+        let automaton = AhoCorasick::new(["fox", "fox"]);
+        assert_eq!(automaton.pattern_count(), 1);
+    }
+}
@@ -0,0 +1,535 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The bipartite graph between an actual container's elements and a set of
+//! matchers, used by matchers such as `unordered_elements_are` and
+//! `contains_each`.
+//!
+//! The maximum matching is computed with the Hopcroft-Karp algorithm, which
+//! runs in `O(E * sqrt(V))` rather than the `O(V * E)` of augmenting one
+//! path at a time, which matters once both the container and the matcher
+//! list are large.
+
+use crate::description::Description;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+
+const UNMATCHED: usize = usize::MAX;
+
+/// A fixed-size set of vertex indices backed by a bit-vector, used for the
+/// per-phase `visited` sets in Hopcroft-Karp so the inner loops stay cheap.
+#[derive(Clone)]
+struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    fn new(len: usize) -> Self {
+        Self { words: vec![0; len.div_ceil(64).max(1)] }
+    }
+
+    fn set(&mut self, index: usize) {
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+}
+
+/// What kind of correspondence between the actual elements and the matchers
+/// is required for the match as a whole to succeed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Requirements {
+    /// Every actual element matches exactly one matcher and vice versa.
+    PerfectMatch,
+    /// Every matcher must match some actual element; actual elements may be
+    /// left over (used by `contains_each`).
+    Superset,
+    /// Every actual element must match some matcher; matchers may be left
+    /// over (used by `is_contained_in`).
+    Subset,
+}
+
+/// Which actual elements and matchers could not be paired up by the
+/// matching computed for a given [`Requirements`], split into vertices with
+/// no edge at all (there is no matcher/element they could ever pair with)
+/// and vertices that do have edges but lost them to some other vertex in
+/// the chosen maximum matching -- a "competition loser," which deserves a
+/// best-match-style explanation rather than a flat "did not match any"
+/// claim, since it's not true.
+pub(crate) struct UnmatchableElements {
+    pub(crate) actual_without_edges: Vec<usize>,
+    /// (actual index, expected indices it has an edge to) for actual
+    /// elements left unmatched despite having a candidate matcher.
+    pub(crate) actual_competing: Vec<(usize, Vec<usize>)>,
+    pub(crate) expected_without_edges: Vec<usize>,
+    /// (expected index, actual indices it has an edge to) for matchers left
+    /// unmatched despite having a candidate element.
+    pub(crate) expected_competing: Vec<(usize, Vec<usize>)>,
+}
+
+/// The bipartite graph between actual elements (left) and matchers (right),
+/// with an edge wherever the matcher matches the element. Edge evaluations
+/// are computed once in [`MatchMatrix::generate`] and memoized here, so
+/// matching never re-invokes a `Matcher`. The `Debug` rendering of each
+/// actual element and the `describe()` text of each matcher are captured at
+/// the same time, so [`Self::explain_unmatchable`] can render real values
+/// instead of internal vertex indices without needing the original slices
+/// passed back in.
+pub(crate) struct MatchMatrix {
+    actual_len: usize,
+    expected_len: usize,
+    /// Row-major `actual_len` x `expected_len` adjacency.
+    matrix: Vec<bool>,
+    actual_debug: Vec<String>,
+    expected_description: Vec<String>,
+}
+
+impl MatchMatrix {
+    /// Builds the match matrix by evaluating `matches` once for every
+    /// (actual, expected) pair. `describe_expected` renders a matcher the
+    /// same way `matches` would describe a match against it (e.g.
+    /// `Matcher::describe(MatcherResult::Match)`), for use in
+    /// [`Self::explain_unmatchable`].
+    pub(crate) fn generate<T: Debug, E>(
+        actual: &[T],
+        expected: &[E],
+        matches: impl Fn(&T, &E) -> bool,
+        describe_expected: impl Fn(&E) -> String,
+    ) -> Self {
+        let actual_len = actual.len();
+        let expected_len = expected.len();
+        let mut matrix = vec![false; actual_len * expected_len];
+        for (actual_index, actual_element) in actual.iter().enumerate() {
+            for (expected_index, expected_element) in expected.iter().enumerate() {
+                matrix[actual_index * expected_len + expected_index] =
+                    matches(actual_element, expected_element);
+            }
+        }
+        Self {
+            actual_len,
+            expected_len,
+            matrix,
+            actual_debug: actual.iter().map(|a| format!("{a:?}")).collect(),
+            expected_description: expected.iter().map(describe_expected).collect(),
+        }
+    }
+
+    fn is_edge(&self, actual_index: usize, expected_index: usize) -> bool {
+        self.matrix[actual_index * self.expected_len + expected_index]
+    }
+
+    /// Whether the matching required by `requirements` exists.
+    pub(crate) fn is_full_match(&self, requirements: Requirements) -> bool {
+        let matching = self.compute_matching();
+        match requirements {
+            Requirements::PerfectMatch => {
+                self.actual_len == self.expected_len
+                    && (0..self.actual_len).all(|i| matching.match_of_actual[i] != UNMATCHED)
+            }
+            Requirements::Superset => {
+                (0..self.expected_len).all(|i| matching.match_of_expected[i] != UNMATCHED)
+            }
+            Requirements::Subset => {
+                (0..self.actual_len).all(|i| matching.match_of_actual[i] != UNMATCHED)
+            }
+        }
+    }
+
+    /// The actual elements and matchers left unpaired by a maximum matching,
+    /// restricted to the side(s) that `requirements` cares about, and split
+    /// into genuinely unmatchable vertices (no edges at all) versus vertices
+    /// that lost a competition for a shared partner.
+    pub(crate) fn find_unmatchable_elements(&self, requirements: Requirements) -> UnmatchableElements {
+        let matching = self.compute_matching();
+
+        let mut actual_without_edges = vec![];
+        let mut actual_competing = vec![];
+        if requirements != Requirements::Superset {
+            for actual_index in 0..self.actual_len {
+                if matching.match_of_actual[actual_index] != UNMATCHED {
+                    continue;
+                }
+                let edges: Vec<usize> =
+                    (0..self.expected_len).filter(|&e| self.is_edge(actual_index, e)).collect();
+                if edges.is_empty() {
+                    actual_without_edges.push(actual_index);
+                } else {
+                    actual_competing.push((actual_index, edges));
+                }
+            }
+        }
+
+        let mut expected_without_edges = vec![];
+        let mut expected_competing = vec![];
+        if requirements != Requirements::Subset {
+            for expected_index in 0..self.expected_len {
+                if matching.match_of_expected[expected_index] != UNMATCHED {
+                    continue;
+                }
+                let edges: Vec<usize> =
+                    (0..self.actual_len).filter(|&a| self.is_edge(a, expected_index)).collect();
+                if edges.is_empty() {
+                    expected_without_edges.push(expected_index);
+                } else {
+                    expected_competing.push((expected_index, edges));
+                }
+            }
+        }
+
+        UnmatchableElements {
+            actual_without_edges,
+            actual_competing,
+            expected_without_edges,
+            expected_competing,
+        }
+    }
+
+    /// A human-readable explanation of why `requirements` isn't satisfied, or
+    /// `None` if it is.
+    ///
+    /// A vertex with no edge at all is reported as genuinely unmatchable
+    /// ("did not match any ..."). A vertex that does have edges but still
+    /// lost its pairing -- because every matcher/element it could pair with
+    /// was claimed by some other vertex in the chosen maximum matching --
+    /// instead gets a best-match-style explanation naming what it did match
+    /// and noting that those were already taken, since claiming it matched
+    /// nothing would be false.
+    pub(crate) fn explain_unmatchable(&self, requirements: Requirements) -> Option<Description> {
+        let unmatchable = self.find_unmatchable_elements(requirements);
+        if unmatchable.actual_without_edges.is_empty()
+            && unmatchable.actual_competing.is_empty()
+            && unmatchable.expected_without_edges.is_empty()
+            && unmatchable.expected_competing.is_empty()
+        {
+            return None;
+        }
+
+        let mut lines = Vec::new();
+
+        if !unmatchable.actual_without_edges.is_empty() {
+            let elements: Vec<&str> =
+                unmatchable.actual_without_edges.iter().map(|&i| self.actual_debug[i].as_str()).collect();
+            lines.push(format!(
+                "The following elements did not match any matcher: {}",
+                elements.join(", ")
+            ));
+        }
+        for (actual_index, expected_indices) in &unmatchable.actual_competing {
+            let candidates: Vec<&str> = expected_indices
+                .iter()
+                .map(|&e| self.expected_description[e].as_str())
+                .collect();
+            let was_were = if candidates.len() == 1 { "was" } else { "were" };
+            lines.push(format!(
+                "The element {} matches {}, but {was_were} already claimed by another element",
+                self.actual_debug[*actual_index],
+                candidates.join(", "),
+            ));
+        }
+
+        if !unmatchable.expected_without_edges.is_empty() {
+            let matchers: Vec<&str> = unmatchable
+                .expected_without_edges
+                .iter()
+                .map(|&i| self.expected_description[i].as_str())
+                .collect();
+            lines.push(format!(
+                "The following matchers did not match any element: {}",
+                matchers.join(", ")
+            ));
+        }
+        for (expected_index, actual_indices) in &unmatchable.expected_competing {
+            let candidates: Vec<&str> =
+                actual_indices.iter().map(|&a| self.actual_debug[a].as_str()).collect();
+            let was_were = if candidates.len() == 1 { "was" } else { "were" };
+            lines.push(format!(
+                "The matcher {} matches {}, but {was_were} already claimed by another matcher",
+                self.expected_description[*expected_index],
+                candidates.join(", "),
+            ));
+        }
+
+        Some(lines.join("\n").into())
+    }
+
+    /// Computes a maximum matching between actual elements and matchers via
+    /// Hopcroft-Karp.
+    ///
+    /// Phase one does a BFS from every currently-unmatched actual element,
+    /// layering the graph by alternating unmatched/matched edges and
+    /// stopping once some unmatched matcher is reached, which gives the
+    /// length of this round's shortest augmenting paths. Phase two does a
+    /// DFS from every unmatched actual element, only descending along edges
+    /// that strictly increase the BFS layer, augmenting every
+    /// vertex-disjoint shortest augmenting path it finds in one sweep. The
+    /// phases alternate until a BFS finds no augmenting path left.
+    fn compute_matching(&self) -> Matching {
+        let mut match_of_actual = vec![UNMATCHED; self.actual_len];
+        let mut match_of_expected = vec![UNMATCHED; self.expected_len];
+        let mut distance = vec![0usize; self.actual_len];
+
+        while self.bfs_layer(&match_of_actual, &match_of_expected, &mut distance) {
+            let mut visited = BitSet::new(self.actual_len.max(1));
+            for actual_index in 0..self.actual_len {
+                if match_of_actual[actual_index] == UNMATCHED {
+                    self.dfs_augment(
+                        actual_index,
+                        &distance,
+                        &mut match_of_actual,
+                        &mut match_of_expected,
+                        &mut visited,
+                    );
+                }
+            }
+        }
+
+        Matching { match_of_actual, match_of_expected }
+    }
+
+    /// Layers the graph by BFS distance from the unmatched actual elements.
+    /// Returns whether some unmatched expected element is reachable, i.e.
+    /// whether another augmenting path exists this round.
+    fn bfs_layer(
+        &self,
+        match_of_actual: &[usize],
+        match_of_expected: &[usize],
+        distance: &mut [usize],
+    ) -> bool {
+        let mut queue = VecDeque::new();
+        for actual_index in 0..self.actual_len {
+            if match_of_actual[actual_index] == UNMATCHED {
+                distance[actual_index] = 0;
+                queue.push_back(actual_index);
+            } else {
+                distance[actual_index] = UNMATCHED;
+            }
+        }
+
+        let mut shortest = UNMATCHED;
+        while let Some(actual_index) = queue.pop_front() {
+            if distance[actual_index] > shortest {
+                break;
+            }
+            for expected_index in 0..self.expected_len {
+                if !self.is_edge(actual_index, expected_index) {
+                    continue;
+                }
+                let matched_actual = match_of_expected[expected_index];
+                if matched_actual == UNMATCHED {
+                    shortest = distance[actual_index];
+                } else if distance[matched_actual] == UNMATCHED {
+                    distance[matched_actual] = distance[actual_index] + 1;
+                    queue.push_back(matched_actual);
+                }
+            }
+        }
+        // Layers past the shortest augmenting path are dropped so that phase
+        // two only follows shortest paths.
+        for layer in distance.iter_mut().filter(|layer| **layer > shortest) {
+            *layer = UNMATCHED;
+        }
+        shortest != UNMATCHED
+    }
+
+    /// Tries to extend the matching with a shortest augmenting path starting
+    /// at `actual_index`, only following edges into the next BFS layer.
+    /// Returns whether it found one.
+    fn dfs_augment(
+        &self,
+        actual_index: usize,
+        distance: &[usize],
+        match_of_actual: &mut [usize],
+        match_of_expected: &mut [usize],
+        visited: &mut BitSet,
+    ) -> bool {
+        visited.set(actual_index);
+        for expected_index in 0..self.expected_len {
+            if !self.is_edge(actual_index, expected_index) {
+                continue;
+            }
+            let matched_actual = match_of_expected[expected_index];
+            let reaches_unmatched = matched_actual == UNMATCHED;
+            let extends_layer = !reaches_unmatched
+                && !visited.contains(matched_actual)
+                && distance[matched_actual] == distance[actual_index] + 1;
+            if reaches_unmatched
+                || (extends_layer
+                    && self.dfs_augment(
+                        matched_actual,
+                        distance,
+                        match_of_actual,
+                        match_of_expected,
+                        visited,
+                    ))
+            {
+                match_of_actual[actual_index] = expected_index;
+                match_of_expected[expected_index] = actual_index;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+struct Matching {
+    match_of_actual: Vec<usize>,
+    match_of_expected: Vec<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix_from_edges(actual_len: usize, expected_len: usize, edges: &[(usize, usize)]) -> MatchMatrix {
+        let actual: Vec<usize> = (0..actual_len).collect();
+        let expected: Vec<usize> = (0..expected_len).collect();
+        MatchMatrix::generate(
+            &actual,
+            &expected,
+            |&a, &e| edges.contains(&(a, e)),
+            |&e| format!("matcher {e}"),
+        )
+    }
+
+    #[test]
+    fn perfect_match_succeeds_when_a_bijection_exists() {
+        let matrix = matrix_from_edges(2, 2, &[(0, 0), (0, 1), (1, 1)]);
+        assert!(matrix.is_full_match(Requirements::PerfectMatch));
+    }
+
+    #[test]
+    fn perfect_match_fails_on_size_mismatch() {
+        let matrix = matrix_from_edges(1, 2, &[(0, 0), (0, 1)]);
+        assert!(!matrix.is_full_match(Requirements::PerfectMatch));
+    }
+
+    #[test]
+    fn perfect_match_fails_without_a_bijection() {
+        // Both actual elements can only match expected element 0.
+        let matrix = matrix_from_edges(2, 2, &[(0, 0), (1, 0)]);
+        assert!(!matrix.is_full_match(Requirements::PerfectMatch));
+    }
+
+    #[test]
+    fn superset_succeeds_when_every_matcher_is_covered() {
+        // Three actual elements, two matchers, one actual element unused.
+        let matrix = matrix_from_edges(3, 2, &[(0, 0), (1, 1), (2, 1)]);
+        assert!(matrix.is_full_match(Requirements::Superset));
+    }
+
+    #[test]
+    fn subset_succeeds_when_every_actual_element_is_covered() {
+        let matrix = matrix_from_edges(2, 3, &[(0, 0), (1, 1)]);
+        assert!(matrix.is_full_match(Requirements::Subset));
+    }
+
+    #[test]
+    fn requires_augmenting_paths_across_multiple_rounds() {
+        // A classic case where a greedy one-path-at-a-time matcher picks a
+        // dead-end pairing on its first attempt: matching actual 0 to
+        // expected 0 (its first edge) leaves actual 1 -- which can only
+        // reach expected 0 -- stuck, even though swapping actual 0 onto
+        // expected 1 (freeing expected 0 for actual 1) yields a perfect
+        // match together with 2-2. This needs an augmenting path through
+        // already-matched vertices, not just a free one.
+        let matrix = matrix_from_edges(3, 3, &[(0, 0), (0, 1), (1, 0), (2, 1), (2, 2)]);
+        assert!(matrix.is_full_match(Requirements::PerfectMatch));
+    }
+
+    #[test]
+    fn find_unmatchable_elements_reports_genuinely_unmatchable_vertices_without_edges() {
+        // Actual 1 and expected 1 have no edge to anything, so they're
+        // genuinely unmatchable, not merely outcompeted.
+        let matrix = matrix_from_edges(2, 2, &[(0, 0)]);
+        let unmatchable = matrix.find_unmatchable_elements(Requirements::PerfectMatch);
+        assert_eq!(unmatchable.actual_without_edges, vec![1]);
+        assert!(unmatchable.actual_competing.is_empty());
+        assert_eq!(unmatchable.expected_without_edges, vec![1]);
+        assert!(unmatchable.expected_competing.is_empty());
+    }
+
+    #[test]
+    fn find_unmatchable_elements_reports_competition_losers_separately() {
+        // Actual 1 does have an edge (to expected 0), it just lost that
+        // pairing to actual 0 in the chosen maximum matching; it must not be
+        // reported as having no edges at all.
+        let matrix = matrix_from_edges(2, 1, &[(0, 0), (1, 0)]);
+        let unmatchable = matrix.find_unmatchable_elements(Requirements::PerfectMatch);
+        assert!(unmatchable.actual_without_edges.is_empty());
+        assert_eq!(unmatchable.actual_competing, vec![(1, vec![0])]);
+    }
+
+    #[test]
+    fn find_unmatchable_elements_reports_expected_side_competition_losers() {
+        // Two matchers competing for the same, sole element: whichever one
+        // the matching doesn't use still has an edge to actual 0.
+        let matrix = matrix_from_edges(1, 2, &[(0, 0), (0, 1)]);
+        let unmatchable = matrix.find_unmatchable_elements(Requirements::Superset);
+        assert_eq!(unmatchable.expected_competing.len(), 1);
+        assert_eq!(unmatchable.expected_competing[0].1, vec![0]);
+    }
+
+    #[test]
+    fn find_unmatchable_elements_ignores_actual_side_for_superset() {
+        let matrix = matrix_from_edges(2, 2, &[(0, 0)]);
+        let unmatchable = matrix.find_unmatchable_elements(Requirements::Superset);
+        assert!(unmatchable.actual_without_edges.is_empty());
+        assert!(unmatchable.actual_competing.is_empty());
+        assert_eq!(unmatchable.expected_without_edges, vec![1]);
+    }
+
+    #[test]
+    fn explain_unmatchable_is_none_on_success() {
+        let matrix = matrix_from_edges(1, 1, &[(0, 0)]);
+        assert!(matrix.explain_unmatchable(Requirements::PerfectMatch).is_none());
+    }
+
+    #[test]
+    fn explain_unmatchable_reports_genuinely_unmatchable_vertex_as_such() {
+        let matrix = matrix_from_edges(1, 2, &[(0, 0)]);
+        let explanation = matrix.explain_unmatchable(Requirements::PerfectMatch).unwrap();
+        let rendered = explanation.to_string();
+        assert!(rendered.contains("did not match any element"));
+        assert!(rendered.contains("matcher 1"));
+    }
+
+    #[test]
+    fn explain_unmatchable_gives_a_best_match_explanation_for_a_competition_loser() {
+        // Both actual elements match expected 0, so whichever one the
+        // matching doesn't use is a competition loser, not an element that
+        // "did not match any matcher" -- that claim would be false since it
+        // does match expected 0.
+        let matrix = matrix_from_edges(2, 1, &[(0, 0), (1, 0)]);
+        let explanation = matrix.explain_unmatchable(Requirements::PerfectMatch).unwrap();
+        let rendered = explanation.to_string();
+        assert!(!rendered.contains("did not match any matcher"));
+        assert!(rendered.contains("matcher 0"));
+        assert!(rendered.contains("already claimed by another element"));
+    }
+
+    #[test]
+    fn explain_unmatchable_renders_real_values_not_indices() {
+        let matrix = matrix_from_edges(1, 2, &[(0, 0)]);
+        let explanation = matrix.explain_unmatchable(Requirements::PerfectMatch).unwrap();
+        assert!(explanation.to_string().contains("matcher 1"));
+    }
+
+    #[test]
+    fn empty_matrix_is_a_trivial_perfect_match() {
+        let matrix = matrix_from_edges(0, 0, &[]);
+        assert!(matrix.is_full_match(Requirements::PerfectMatch));
+    }
+}
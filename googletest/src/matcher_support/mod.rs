@@ -19,6 +19,7 @@
 //! matchers.
 
 mod auto_eq;
+pub(crate) mod aho_corasick;
 pub(crate) mod count_elements;
 pub(crate) mod edit_distance;
 pub(crate) mod match_matrix;
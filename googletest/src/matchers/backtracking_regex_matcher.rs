@@ -0,0 +1,179 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A regex matcher backed by a backtracking engine, for patterns the
+//! `regex` crate deliberately can't express.
+//!
+//! This whole module is gated behind the `fancy-regex` feature so that the
+//! default dependency set -- and the linear-time matching guarantee of the
+//! `regex`-backed matchers -- is unchanged for users who don't opt in.
+#![cfg(feature = "fancy-regex")]
+
+use crate::{
+    description::Description,
+    matcher::{Matcher, MatcherBase, MatcherResult},
+};
+use fancy_regex::Regex;
+use std::fmt::Debug;
+
+/// Matches a string against `pattern`, compiled with a backtracking regex
+/// engine supporting backreferences (`\1`) and lookaround
+/// (`(?=...)`/`(?<!...)`), which the `regex` crate rejects at compile time.
+///
+/// Prefer [`matches_regex`][crate::matchers::matches_regex] unless the
+/// assertion genuinely needs a backreference or lookaround: a backtracking
+/// engine can take exponential time on adversarial input, whereas `regex`
+/// is guaranteed linear.
+///
+/// ```ignore
+/// verify_that!("abcabc", matches_pattern_with_backrefs(r"(abc)\1"))?;
+/// ```
+///
+/// A malformed `pattern` does not panic; it instead produces a matcher
+/// which never matches, whose `describe` reports the compilation error and
+/// whose `explain_match` states that the pattern failed to compile, so the
+/// assertion fails with a readable message.
+pub fn matches_pattern_with_backrefs(
+    pattern: impl AsRef<str>,
+) -> MatchesPatternWithBackrefsMatcher {
+    let pattern = pattern.as_ref();
+    // A second copy of the pattern anchored to the start of the string, used
+    // only for the "longest matched prefix" diagnostic below, so that a
+    // prefix is reported only when the pattern matches it starting from its
+    // first character rather than somewhere inside it.
+    let anchored_regex = Regex::new(&format!(r"\A(?:{pattern})")).map_err(|e| e.to_string());
+    MatchesPatternWithBackrefsMatcher {
+        regex: Regex::new(pattern).map_err(|e| e.to_string()),
+        anchored_regex,
+    }
+}
+
+#[derive(MatcherBase)]
+pub struct MatchesPatternWithBackrefsMatcher {
+    regex: Result<Regex, String>,
+    anchored_regex: Result<Regex, String>,
+}
+
+impl<ActualT: AsRef<str> + Debug + ?Sized> Matcher<&ActualT> for MatchesPatternWithBackrefsMatcher {
+    fn matches(&self, actual: &ActualT) -> MatcherResult {
+        match &self.regex {
+            Ok(regex) => regex.is_match(actual.as_ref()).unwrap_or(false).into(),
+            Err(_) => MatcherResult::NoMatch,
+        }
+    }
+
+    fn explain_match(&self, actual: &ActualT) -> Description {
+        let Ok(regex) = &self.regex else {
+            return "which could not be checked because the pattern failed to compile".into();
+        };
+        let actual = actual.as_ref();
+        match regex.is_match(actual) {
+            Ok(true) => "which matches".into(),
+            _ => {
+                let Ok(anchored_regex) = &self.anchored_regex else {
+                    return "which does not match".into();
+                };
+                match longest_matching_prefix(anchored_regex, actual) {
+                    "" => "which does not match".into(),
+                    prefix => format!(
+                        "which does not match, though the pattern did match the leading \
+                         {prefix:?} before backtracking failed"
+                    )
+                    .into(),
+                }
+            }
+        }
+    }
+
+    fn describe(&self, matcher_result: MatcherResult) -> Description {
+        match &self.regex {
+            Err(error) => {
+                format!("is a string matching a pattern, but the pattern failed to compile: {error}")
+                    .into()
+            }
+            Ok(regex) => match matcher_result {
+                MatcherResult::Match => format!("matches the pattern {:?}", regex.as_str()).into(),
+                MatcherResult::NoMatch => {
+                    format!("doesn't match the pattern {:?}", regex.as_str()).into()
+                }
+            },
+        }
+    }
+}
+
+/// Finds the longest prefix of `actual` on which `anchored_regex` -- the
+/// user's pattern wrapped in a `\A` start-of-string anchor -- matches,
+/// trying successively shorter prefixes, or `""` if there is none. This does
+/// not trace the engine's own backtracking: a pattern that matches a prefix
+/// also matches the whole string unless it inspects the text after that
+/// prefix (`$`, `\b`, a lookahead), so for a pattern without such an
+/// assertion that fails on the whole string the result is `""`.
+fn longest_matching_prefix<'a>(anchored_regex: &Regex, actual: &'a str) -> &'a str {
+    for end in (0..=actual.len()).rev() {
+        let Some(prefix) = actual.get(..end) else { continue };
+        if matches!(anchored_regex.is_match(prefix), Ok(true)) {
+            return prefix;
+        }
+    }
+    ""
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::Result;
+
+    #[test]
+    fn matches_backreference() -> Result<()> {
+        verify_that!("abcabc", matches_pattern_with_backrefs(r"(abc)\1"))
+    }
+
+    #[test]
+    fn does_not_match_when_backreference_differs() -> Result<()> {
+        verify_that!("abcabd", not(matches_pattern_with_backrefs(r"(abc)\1")))
+    }
+
+    #[test]
+    fn matches_negative_lookbehind() -> Result<()> {
+        verify_that!("cat", matches_pattern_with_backrefs(r"(?<!s)cat"))
+    }
+
+    #[test]
+    fn does_not_match_when_lookbehind_excludes_it() -> Result<()> {
+        verify_that!("scat", not(matches_pattern_with_backrefs(r"(?<!s)cat")))
+    }
+
+    #[test]
+    fn reports_compile_error_instead_of_panicking() -> Result<()> {
+        let result = verify_that!("anything", matches_pattern_with_backrefs(r"(unterminated"));
+
+        verify_that!(result, err(displays_as(contains_substring("failed to compile"))))
+    }
+
+    #[test]
+    fn explain_match_reports_longest_matching_prefix_when_full_match_fails() -> Result<()> {
+        // `abc$` is an ordinary (unanchored-at-start) pattern: it matches
+        // somewhere in a string ending in "abc". It matches the "abc" prefix
+        // of "abcd" on its own, but not all of "abcd", so the diagnostic
+        // should surface that prefix rather than report a bare non-match.
+        let result = verify_that!("abcd", matches_pattern_with_backrefs(r"abc$"));
+
+        verify_that!(
+            result,
+            err(displays_as(contains_substring(
+                "did match the leading \"abc\" before backtracking failed"
+            )))
+        )
+    }
+}
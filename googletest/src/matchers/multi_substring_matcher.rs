@@ -0,0 +1,206 @@
+// Copyright 2024 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    description::Description,
+    matcher::{Matcher, MatcherBase, MatcherResult},
+    matcher_support::aho_corasick::AhoCorasick,
+};
+use std::fmt::Debug;
+
+/// Matches a string which contains every one of `patterns` as a substring.
+///
+/// The string is scanned once against the whole pattern set via an
+/// Aho-Corasick automaton, so checking for many fragments is much cheaper
+/// than chaining that many [`contains_substring`][
+/// crate::matchers::contains_substring] matchers, each of which would
+/// rescan the whole string.
+///
+/// ```
+/// # use googletest::prelude::*;
+/// # fn should_pass() -> googletest::Result<()> {
+/// verify_that!("A string with a substring", contains_all_substrings(["with", "substring"]))?;
+/// #     Ok(())
+/// # }
+/// # fn should_fail() -> googletest::Result<()> {
+/// verify_that!("A string", contains_all_substrings(["with", "substring"]))?;
+/// #     Ok(())
+/// # }
+/// # should_pass().unwrap();
+/// # should_fail().unwrap_err();
+/// ```
+pub fn contains_all_substrings<'a>(
+    patterns: impl IntoIterator<Item = &'a str>,
+) -> ContainsAllSubstringsMatcher {
+    ContainsAllSubstringsMatcher { automaton: AhoCorasick::new(patterns) }
+}
+
+/// Matches a string which contains at least one of `patterns` as a
+/// substring.
+///
+/// Like [`contains_all_substrings`], the string is scanned only once against
+/// the whole pattern set.
+///
+/// ```
+/// # use googletest::prelude::*;
+/// # fn should_pass() -> googletest::Result<()> {
+/// verify_that!("A string with a substring", contains_any_substring(["missing", "substring"]))?;
+/// #     Ok(())
+/// # }
+/// # fn should_fail() -> googletest::Result<()> {
+/// verify_that!("A string", contains_any_substring(["missing", "absent"]))?;
+/// #     Ok(())
+/// # }
+/// # should_pass().unwrap();
+/// # should_fail().unwrap_err();
+/// ```
+pub fn contains_any_substring<'a>(
+    patterns: impl IntoIterator<Item = &'a str>,
+) -> ContainsAnySubstringMatcher {
+    ContainsAnySubstringMatcher { automaton: AhoCorasick::new(patterns) }
+}
+
+#[derive(MatcherBase)]
+pub struct ContainsAllSubstringsMatcher {
+    automaton: AhoCorasick,
+}
+
+impl<ActualT: AsRef<str> + Debug + ?Sized> Matcher<&ActualT> for ContainsAllSubstringsMatcher {
+    fn matches(&self, actual: &ActualT) -> MatcherResult {
+        self.automaton.find_all(actual.as_ref()).into_iter().all(|found| found).into()
+    }
+
+    fn explain_match(&self, actual: &ActualT) -> Description {
+        let found = self.automaton.find_all(actual.as_ref());
+        let missing: Vec<&str> = self
+            .automaton
+            .patterns()
+            .iter()
+            .zip(found.iter())
+            .filter(|(_, &found)| !found)
+            .map(|(pattern, _)| pattern.as_str())
+            .collect();
+        if missing.is_empty() {
+            "which contains all expected substrings".into()
+        } else {
+            format!("which is missing the substring(s) {missing:?}").into()
+        }
+    }
+
+    fn describe(&self, matcher_result: MatcherResult) -> Description {
+        match matcher_result {
+            MatcherResult::Match => {
+                format!("contains all of the substrings {:?}", self.automaton.patterns()).into()
+            }
+            MatcherResult::NoMatch => format!(
+                "does not contain all of the substrings {:?}",
+                self.automaton.patterns()
+            )
+            .into(),
+        }
+    }
+}
+
+#[derive(MatcherBase)]
+pub struct ContainsAnySubstringMatcher {
+    automaton: AhoCorasick,
+}
+
+impl<ActualT: AsRef<str> + Debug + ?Sized> Matcher<&ActualT> for ContainsAnySubstringMatcher {
+    fn matches(&self, actual: &ActualT) -> MatcherResult {
+        if self.automaton.pattern_count() == 0 {
+            return MatcherResult::NoMatch;
+        }
+        self.automaton.find_all(actual.as_ref()).into_iter().any(|found| found).into()
+    }
+
+    fn explain_match(&self, actual: &ActualT) -> Description {
+        let found = self.automaton.find_all(actual.as_ref());
+        let found_patterns: Vec<&str> = self
+            .automaton
+            .patterns()
+            .iter()
+            .zip(found.iter())
+            .filter(|(_, &found)| found)
+            .map(|(pattern, _)| pattern.as_str())
+            .collect();
+        if found_patterns.is_empty() {
+            "which contains none of the expected substrings".into()
+        } else {
+            format!("which contains the substring(s) {found_patterns:?}").into()
+        }
+    }
+
+    fn describe(&self, matcher_result: MatcherResult) -> Description {
+        match matcher_result {
+            MatcherResult::Match => format!(
+                "contains at least one of the substrings {:?}",
+                self.automaton.patterns()
+            )
+            .into(),
+            MatcherResult::NoMatch => {
+                format!("does not contain any of the substrings {:?}", self.automaton.patterns())
+                    .into()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::Result;
+
+    #[test]
+    fn contains_all_substrings_matches_when_all_present() -> Result<()> {
+        verify_that!("a quick brown fox", contains_all_substrings(["quick", "fox"]))
+    }
+
+    #[test]
+    fn contains_all_substrings_does_not_match_when_one_missing() -> Result<()> {
+        verify_that!("a quick brown fox", not(contains_all_substrings(["quick", "dog"])))
+    }
+
+    #[test]
+    fn contains_all_substrings_matches_trivially_for_empty_pattern_set() -> Result<()> {
+        verify_that!("anything", contains_all_substrings(Vec::<&str>::new()))
+    }
+
+    #[test]
+    fn contains_all_substrings_deduplicates_patterns() -> Result<()> {
+        verify_that!("fox", contains_all_substrings(["fox", "fox"]))
+    }
+
+    #[test]
+    fn contains_all_substrings_explain_match_lists_missing_substrings() -> Result<()> {
+        let result = verify_that!("a quick brown fox", contains_all_substrings(["quick", "dog"]));
+
+        verify_that!(result, err(displays_as(contains_substring("missing the substring(s)"))))
+    }
+
+    #[test]
+    fn contains_any_substring_matches_when_one_present() -> Result<()> {
+        verify_that!("a quick brown fox", contains_any_substring(["dog", "fox"]))
+    }
+
+    #[test]
+    fn contains_any_substring_does_not_match_when_none_present() -> Result<()> {
+        verify_that!("a quick brown fox", not(contains_any_substring(["dog", "cat"])))
+    }
+
+    #[test]
+    fn contains_any_substring_does_not_match_for_empty_pattern_set() -> Result<()> {
+        verify_that!("anything", not(contains_any_substring(Vec::<&str>::new())))
+    }
+}